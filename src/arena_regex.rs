@@ -1,14 +1,15 @@
+use std::collections::HashMap;
 use typed_arena::Arena;
 
 pub struct RegexStorage<'a>(Arena<Regex<'a>>);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Regex<'a> {
     nullable: bool,
     contents: RegexContents<'a>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum RegexContents<'a> {
     Void,
     Epsilon,
@@ -16,6 +17,8 @@ enum RegexContents<'a> {
     CharSet(char, char),
     Seq(&'a Regex<'a>, &'a Regex<'a>),
     Alt(&'a Regex<'a>, &'a Regex<'a>),
+    And(&'a Regex<'a>, &'a Regex<'a>),
+    Not(&'a Regex<'a>),
     Star(&'a Regex<'a>),
 }
 
@@ -50,6 +53,8 @@ impl<'a> RegexStorage<'a> {
             Seq(x, y) if x.nullable => self.alt(self.seq(self.deriv(c, *x), *y), self.deriv(c, *y)),
             Seq(x, y) => self.seq(self.deriv(c, *x), *y),
             Alt(x, y) => self.alt(self.deriv(c, *x), self.deriv(c, *y)),
+            And(x, y) => self.and(self.deriv(c, *x), self.deriv(c, *y)),
+            Not(x) => self.not(self.deriv(c, *x)),
             Star(x) => self.seq(self.deriv(c, *x), regex),
         }
     }
@@ -103,22 +108,120 @@ impl<'a> RegexStorage<'a> {
     pub fn alt(&'a self, x: Regex<'a>, y: Regex<'a>) -> Regex<'a> {
         use RegexContents::*;
 
-        match (x.contents, y.contents) {
-            (Void, _) => y,
-            (_, Void) => x,
-            (Epsilon, _) if y.nullable => y,
-            (_, Epsilon) if x.nullable => x,
-            (_, _) => {
+        // Normalize `Alt` under associativity, commutativity, and idempotence so that
+        // structurally-equal alternations share a single representation. This keeps the derivative
+        // state set finite (see `Dfa`): `a*`-style patterns would otherwise spawn unboundedly many
+        // distinct-but-equivalent `Alt` trees.
+        let mut leaves = Vec::new();
+        self.collect_alt(x, &mut leaves);
+        self.collect_alt(y, &mut leaves);
+        leaves.retain(|r| r.contents != Void);
+        leaves.sort();
+        leaves.dedup();
+        // `epsilon | r` collapses to `r` whenever `r` already matches the empty string.
+        if leaves.len() > 1 && leaves.iter().any(|r| r.nullable && r.contents != Epsilon) {
+            leaves.retain(|r| r.contents != Epsilon);
+        }
+
+        let mut leaves = leaves.into_iter().rev();
+        let mut acc = match leaves.next() {
+            Some(acc) => acc,
+            None => return self.void(),
+        };
+        for leaf in leaves {
+            let leaf = self.0.alloc(leaf);
+            let acc_ref = self.0.alloc(acc);
+            acc = Regex {
+                nullable: leaf.nullable || acc_ref.nullable,
+                contents: Alt(leaf, acc_ref),
+            };
+        }
+        acc
+    }
+
+    fn collect_alt(&self, regex: Regex<'a>, leaves: &mut Vec<Regex<'a>>) {
+        match regex.contents {
+            RegexContents::Alt(x, y) => {
+                self.collect_alt(*x, leaves);
+                self.collect_alt(*y, leaves);
+            }
+            _ => leaves.push(regex),
+        }
+    }
+
+    /// The regex matching every string, i.e. `!Void`. `nullable` because it matches the empty
+    /// string, and its own derivative, so it is a single fixed DFA state.
+    pub fn any(&'a self) -> Regex<'a> {
+        self.not(self.void())
+    }
+
+    /// Intersection: matches exactly the strings matched by both `x` and `y`. Normalized under
+    /// associativity, commutativity, and idempotence (like `alt`) so the derivative state set stays
+    /// finite.
+    pub fn and(&'a self, x: Regex<'a>, y: Regex<'a>) -> Regex<'a> {
+        use RegexContents::*;
+
+        if x.contents == Void || y.contents == Void {
+            return self.void();
+        }
+        let mut leaves = Vec::new();
+        self.collect_and(x, &mut leaves);
+        self.collect_and(y, &mut leaves);
+        // `any` is the identity of intersection.
+        leaves.retain(|r| !self.is_any(*r));
+        leaves.sort();
+        leaves.dedup();
+
+        let mut leaves = leaves.into_iter().rev();
+        let mut acc = match leaves.next() {
+            Some(acc) => acc,
+            None => return self.any(),
+        };
+        for leaf in leaves {
+            let leaf = self.0.alloc(leaf);
+            let acc_ref = self.0.alloc(acc);
+            acc = Regex {
+                nullable: leaf.nullable && acc_ref.nullable,
+                contents: And(leaf, acc_ref),
+            };
+        }
+        acc
+    }
+
+    fn collect_and(&self, regex: Regex<'a>, leaves: &mut Vec<Regex<'a>>) {
+        match regex.contents {
+            RegexContents::And(x, y) => {
+                self.collect_and(*x, leaves);
+                self.collect_and(*y, leaves);
+            }
+            _ => leaves.push(regex),
+        }
+    }
+
+    /// Complement: matches exactly the strings `x` does not. Double negation is elided so that
+    /// `!!x` and `x` are the same DFA state.
+    pub fn not(&'a self, x: Regex<'a>) -> Regex<'a> {
+        use RegexContents::*;
+
+        match x.contents {
+            Not(inner) => *inner,
+            _ => {
                 let x = self.0.alloc(x);
-                let y = self.0.alloc(y);
                 Regex {
-                    nullable: x.nullable || y.nullable,
-                    contents: Alt(x, y),
+                    nullable: !x.nullable,
+                    contents: Not(x),
                 }
             }
         }
     }
 
+    fn is_any(&self, regex: Regex<'a>) -> bool {
+        match regex.contents {
+            RegexContents::Not(inner) => inner.contents == RegexContents::Void,
+            _ => false,
+        }
+    }
+
     pub fn star(&'a self, x: Regex<'a>) -> Regex<'a> {
         use RegexContents::*;
 
@@ -136,6 +239,92 @@ impl<'a> RegexStorage<'a> {
     }
 }
 
+/// A lazily-constructed DFA over the Brzozowski derivatives of a regex.
+///
+/// Rather than recomputing a fresh derivative for every character of every input (as
+/// `RegexStorage::matches` does), a `Dfa` determinizes the derivative automaton on the fly and
+/// caches the result. Each reachable derivative is hash-consed to a `StateId`; the transition table
+/// is filled in lazily and reused across calls, so after warm-up matching is a tight loop of array
+/// lookups with no allocation.
+///
+/// Finiteness relies on identifying structurally-equal derivatives: interning keys on the
+/// structural `Eq`/`Hash` of `Regex` (not on arena pointer identity), and `alt` keeps alternations
+/// in a canonical normal form.
+pub struct Dfa<'a> {
+    storage: &'a RegexStorage<'a>,
+    states: Vec<Regex<'a>>,
+    intern: HashMap<Regex<'a>, StateId>,
+    transitions: Vec<[StateId; 256]>,
+}
+
+/// An index into a `Dfa`'s state table. State 0 is always the regex the `Dfa` was built from.
+pub type StateId = usize;
+
+/// Sentinel marking a transition that has not been computed yet.
+const UNKNOWN: StateId = StateId::MAX;
+
+impl<'a> Dfa<'a> {
+    /// Build a lazy DFA recognizing `regex`, allocating derivatives into `storage`.
+    pub fn new(storage: &'a RegexStorage<'a>, regex: Regex<'a>) -> Dfa<'a> {
+        let mut dfa = Dfa {
+            storage,
+            states: Vec::new(),
+            intern: HashMap::new(),
+            transitions: Vec::new(),
+        };
+        dfa.intern(regex);
+        dfa
+    }
+
+    /// Does the entire input match?
+    ///
+    /// The automaton is driven one byte at a time, each byte fed to `deriv` as `byte as char`, so
+    /// the transition table stays byte-indexed and every step is a single array lookup. For patterns
+    /// and inputs restricted to ASCII this agrees with `RegexStorage::matches`; it is *not*
+    /// equivalent when the pattern contains non-ASCII `char`s (e.g. `char('Ā')`), since
+    /// `RegexStorage::matches` derives against whole `char`s while the `Dfa` sees their individual
+    /// UTF-8 bytes.
+    pub fn matches(&mut self, input: &str) -> bool {
+        let mut state = 0;
+        for byte in input.bytes() {
+            state = self.transition(state, byte);
+            if self.is_dead(state) {
+                return false;
+            }
+        }
+        self.states[state].nullable
+    }
+
+    /// Look up `transition[state][byte]`, computing and caching the derivative if it is unknown.
+    fn transition(&mut self, state: StateId, byte: u8) -> StateId {
+        let cached = self.transitions[state][byte as usize];
+        if cached != UNKNOWN {
+            return cached;
+        }
+        let next = self.storage.deriv(byte as char, self.states[state]);
+        let next = self.intern(next);
+        self.transitions[state][byte as usize] = next;
+        next
+    }
+
+    /// Intern `regex`, returning its existing `StateId` or allocating a fresh one.
+    fn intern(&mut self, regex: Regex<'a>) -> StateId {
+        if let Some(&id) = self.intern.get(&regex) {
+            return id;
+        }
+        let id = self.states.len();
+        self.states.push(regex);
+        self.transitions.push([UNKNOWN; 256]);
+        self.intern.insert(regex, id);
+        id
+    }
+
+    /// A state is dead iff its regex is `Void`: no extension can ever match.
+    fn is_dead(&self, state: StateId) -> bool {
+        self.states[state].contents == RegexContents::Void
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,6 +355,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn dfa() {
+        let storage = RegexStorage::new();
+        let zero = storage.char('0');
+        let one = storage.char('1');
+        let dot = storage.char('.');
+        let epsilon = storage.epsilon();
+        let digit = storage.char_set('0', '1');
+        let digits = storage.star(digit);
+        let leading = storage.alt(zero, storage.seq(one, digits));
+        let trailing = storage.alt(epsilon, storage.seq(dot, digits));
+        let number = storage.seq(leading, trailing);
+
+        let mut dfa = Dfa::new(&storage, number);
+        assert!(dfa.matches("1"));
+        assert!(dfa.matches("1.0"));
+        assert!(!dfa.matches(".0"));
+        assert!(dfa.matches(ANUM));
+        assert!(!dfa.matches(NOTANUM));
+
+        // The state set stays finite no matter how long the input is.
+        let long = "1".to_string() + &"0".repeat(10000);
+        assert!(dfa.matches(&long));
+    }
+
+    #[test]
+    fn intersection_and_complement() {
+        let storage = RegexStorage::new();
+        let digit = storage.char_set('0', '1');
+        let binary = storage.star(digit);
+
+        // `any` matches everything.
+        let any = storage.any();
+        assert!(storage.matches("", any));
+        assert!(storage.matches("hello", any));
+
+        // Intersection: a non-empty binary string.
+        let nonempty = storage.not(storage.epsilon());
+        let nonempty_binary = storage.and(binary, nonempty);
+        assert!(storage.matches("0", nonempty_binary));
+        assert!(storage.matches("1011", nonempty_binary));
+        assert!(!storage.matches("", nonempty_binary));
+        assert!(!storage.matches("012", nonempty_binary));
+
+        // Complement: any string that is not all ones.
+        let ones = storage.star(storage.char('1'));
+        let not_all_ones = storage.not(ones);
+        assert!(storage.matches("0", not_all_ones));
+        assert!(storage.matches("110", not_all_ones));
+        assert!(!storage.matches("", not_all_ones));
+        assert!(!storage.matches("111", not_all_ones));
+
+        // Double negation collapses, and intersection is idempotent.
+        assert_eq!(storage.not(storage.not(binary)), binary);
+        assert_eq!(storage.and(binary, binary), binary);
+    }
+
     // Burnt Sushi's Regexes.
     // 20 times faster on this example.
     #[test]
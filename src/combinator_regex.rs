@@ -1,3 +1,7 @@
+use smallvec::SmallVec;
+use std::cell::Cell;
+use std::rc::Rc;
+
 /// A trait for Regex combinators. The key to combinators is a shared interface.
 ///
 /// This interface allows for `O(NM)` regex parsing. It took me a few attempts to find it. My first
@@ -13,39 +17,235 @@
 ///
 /// **Definition.** At any time, this state is "tracking" a set of strings:
 ///
-/// - The state constructed by `Regex::init_state()` tracks an empty set of strings.
-/// - The `start()` method adds the empty string to the tracking set.
+/// - The state constructed by `Regex::initialize()` tracks an empty set of strings.
+/// - The `seed()` method adds the empty string to the tracking set.
 /// - The `advance(u8)` method appends the char to each string in the tracking set.
 ///
-/// **Requirement.** The `accepts()` method returns true iff the `Regex` accepts any of the strings
-/// in its tracking set.
-pub trait Regex: Clone {
+/// Each tracked string carries a [`Thread`]: the byte offset at which it was seeded (so that `find`
+/// can report where a match began) plus the capture slots stamped by any enclosing `group`. When
+/// several tracked strings would collapse to the same NFA state, they are merged into a single
+/// winning thread, leftmost-first, exactly as a PikeVM deduplicates threads by program counter.
+///
+/// **Requirement.** The `accept()` method returns the winning thread iff the `Regex` accepts any of
+/// the strings in its tracking set.
+pub trait Regex {
     /// Reset to the initial, _empty_ state. In NFA terms, this is an empty set of states.
     fn initialize(&mut self);
-    /// Track an empty string.
-    fn start(&mut self);
-    /// Append `byte` to every string being tracked.
-    fn advance(&mut self, byte: u8);
-    /// Does the regex match any of the tracked strings?
-    fn accepts(&self) -> bool;
-    /// Is it true that both (i) accepts() is false, and (ii) accepts() will remain false for any
+    /// Track the empty string carried by `thread`, which is currently sitting at byte position
+    /// `pos`. Merging collapses threads that reach the same NFA state to a single leftmost winner.
+    fn seed(&mut self, pos: usize, thread: &Thread);
+    /// Append a byte to every string being tracked, identified by its equivalence-`class` id (see
+    /// [`ByteClasses`]); `pos` is the byte position _after_ the byte. Leaf regexes dispatch on the
+    /// class with a single array index instead of re-evaluating their predicate per byte.
+    fn advance(&mut self, pos: usize, class: u8);
+    /// The winning thread among the tracked strings the regex accepts, or `None` if it accepts none.
+    fn accept(&self) -> Option<Thread>;
+    /// Is it true that both (i) accept() is None, and (ii) accept() will remain None for any
     /// possible sequence of `advance`s? This is used for a short-circuiting optimization.
     fn is_dead(&self) -> bool;
 
+    /// Register the byte boundaries of every predicate in this pattern into `set`. Leaf regexes
+    /// register their predicate; composite regexes recurse into their children.
+    fn register_bytes(&self, set: &mut ByteClassSet) {
+        let _ = set;
+    }
+
+    /// Distribute the pattern-wide `classes` down to the leaves before matching, so that each
+    /// `advance` can dispatch on a class id instead of re-evaluating a predicate. Leaf regexes
+    /// precompute which classes their predicate accepts; composite regexes recurse into their
+    /// children. The default is a no-op for leaves that carry no predicate.
+    fn install_classes(&mut self, classes: &ByteClasses) {
+        let _ = classes;
+    }
+
+    /// The number of capture slots this pattern needs: two (open, close) per group index reachable.
+    fn slot_count(&self) -> usize {
+        0
+    }
+
+    /// Track an empty string. Equivalent to seeding at offset 0.
+    fn start(&mut self) {
+        self.seed(0, &Thread::new(0, self.slot_count()));
+    }
+
+    /// Partition the 256 byte values into equivalence classes for this whole pattern.
+    fn byte_classes(&self) -> ByteClasses {
+        let mut set = ByteClassSet::new();
+        self.register_bytes(&mut set);
+        set.byte_classes()
+    }
+
+    /// Does the regex match any of the tracked strings?
+    fn accepts(&self) -> bool {
+        self.accept().is_some()
+    }
+
     /// Does the input match this regex? Note that this is not looking for an occurrence of the
     /// Regex pattern _somewhere_ in the input; it's specifically checking that the _entire input_
     /// matches the regex.
     fn is_match(&mut self, input: &str) -> bool {
         self.initialize();
+        let classes = self.byte_classes();
+        self.install_classes(&classes);
         self.start();
-        for byte in input.bytes() {
-            self.advance(byte);
+        for (i, byte) in input.bytes().enumerate() {
+            self.advance(i + 1, classes.get(byte));
             if self.is_dead() {
                 return false;
             }
         }
         self.accepts()
     }
+
+    /// Find the leftmost-longest substring of `input` that matches, returned as a `(start, end)`
+    /// byte range. Unlike `is_match`, this searches for an occurrence _somewhere_ in the input.
+    ///
+    /// A fresh potential match is seeded at every byte offset, so a single forward pass tracks every
+    /// candidate start simultaneously. Each accepting state reports its winning thread, whose start
+    /// is the smallest live start-offset; the leftmost such start wins, and for a fixed start the
+    /// longest extension wins.
+    fn find(&mut self, input: &str) -> Option<(usize, usize)> {
+        self.initialize();
+        let classes = self.byte_classes();
+        self.install_classes(&classes);
+        let slots = self.slot_count();
+        let bytes = input.as_bytes();
+        let mut best: Option<(usize, usize)> = None;
+        for offset in 0..=bytes.len() {
+            self.seed(offset, &Thread::new(offset, slots));
+            best = improve(best, self.accept().map(|t| t.start), offset);
+            if offset == bytes.len() {
+                break;
+            }
+            self.advance(offset + 1, classes.get(bytes[offset]));
+            best = improve(best, self.accept().map(|t| t.start), offset + 1);
+            // Once a match exists and nothing is still alive to extend it, no later seed can start
+            // earlier, so we are done.
+            if best.is_some() && self.is_dead() {
+                break;
+            }
+        }
+        best
+    }
+
+    /// Match the _entire input_ and extract submatches. Returns the `(start, end)` byte range of
+    /// each `group`, indexed by group index, or `None` if the input doesn't match.
+    fn captures(&mut self, input: &str) -> Option<Vec<Option<(usize, usize)>>> {
+        self.initialize();
+        let classes = self.byte_classes();
+        self.install_classes(&classes);
+        let slots = self.slot_count();
+        self.seed(0, &Thread::new(0, slots));
+        for (i, byte) in input.bytes().enumerate() {
+            self.advance(i + 1, classes.get(byte));
+            if self.is_dead() {
+                return None;
+            }
+        }
+        let thread = self.accept()?;
+        let mut groups = Vec::with_capacity(slots / 2);
+        for group in 0..slots / 2 {
+            groups.push(match (thread.slot(2 * group), thread.slot(2 * group + 1)) {
+                (Some(start), Some(end)) => Some((start, end)),
+                _ => None,
+            });
+        }
+        Some(groups)
+    }
+}
+
+/// A monotonically increasing priority stamp, handed out in the order threads are forked. Because
+/// `Alt::seed` seeds its left child before demoting the right one, a left-branch (or earlier)
+/// thread always carries a smaller stamp than the right-branch (or later) thread it competes with,
+/// which is exactly the leftmost-first priority a PikeVM enforces by thread order.
+thread_local! {
+    static PRIORITY: Cell<u64> = const { Cell::new(0) };
+}
+
+fn next_priority() -> u64 {
+    PRIORITY.with(|p| {
+        let next = p.get();
+        p.set(next + 1);
+        next
+    })
+}
+
+/// The payload carried by a tracked string: the byte offset at which it began, a priority stamp
+/// breaking ties between equal-start threads leftmost-first, plus the capture slots stamped so far
+/// (two per group: open then close). The slots are behind an `Rc` so that the clone performed on
+/// every merge is a cheap refcount bump rather than a `Vec` copy.
+#[derive(Clone)]
+pub struct Thread {
+    start: usize,
+    priority: u64,
+    slots: Rc<Vec<Option<usize>>>,
+}
+
+impl Thread {
+    fn new(start: usize, slot_count: usize) -> Thread {
+        Thread {
+            start,
+            priority: next_priority(),
+            slots: Rc::new(vec![None; slot_count]),
+        }
+    }
+
+    fn slot(&self, index: usize) -> Option<usize> {
+        self.slots[index]
+    }
+
+    /// A copy of this thread demoted below every thread forked so far: used for the right branch of
+    /// an `Alt`, so the left branch keeps priority on an equal-start tie.
+    fn demote(&self) -> Thread {
+        Thread {
+            start: self.start,
+            priority: next_priority(),
+            slots: Rc::clone(&self.slots),
+        }
+    }
+
+    /// A copy of this thread with `slot` stamped to `pos` (copy-on-write on the shared slot vector).
+    fn stamp(&self, slot: usize, pos: usize) -> Thread {
+        let mut slots = (*self.slots).clone();
+        slots[slot] = Some(pos);
+        Thread {
+            start: self.start,
+            priority: self.priority,
+            slots: Rc::new(slots),
+        }
+    }
+}
+
+/// Merge two threads that have collapsed to the same NFA state, keeping the leftmost-first winner:
+/// the earlier start wins, and on an equal-start tie the smaller priority stamp wins (so left `Alt`
+/// branches and earlier-seeded threads take priority regardless of evaluation order), making
+/// captures deterministic.
+fn merge(a: Option<Thread>, b: Option<Thread>) -> Option<Thread> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            if (b.start, b.priority) < (a.start, a.priority) {
+                Some(b)
+            } else {
+                Some(a)
+            }
+        }
+        (Some(a), None) => Some(a),
+        (None, b) => b,
+    }
+}
+
+/// Fold a newly-observed accepting match (starting at `start`, ending at `end`) into the best match
+/// seen so far, preferring smaller starts and, for equal starts, longer matches.
+fn improve(best: Option<(usize, usize)>, start: Option<usize>, end: usize) -> Option<(usize, usize)> {
+    let start = match start {
+        Some(start) => start,
+        None => return best,
+    };
+    match best {
+        Some((bs, _)) if start > bs => best,
+        Some((bs, be)) if start == bs && end <= be => best,
+        _ => Some((start, end)),
+    }
 }
 
 /*******************/
@@ -54,11 +254,84 @@ pub trait Regex: Clone {
 
 trait Predicate: Copy {
     fn matches(&self, byte: u8) -> bool;
+    /// Register the byte boundaries this predicate cares about, so that `ByteClasses` can place
+    /// bytes it treats identically into the same equivalence class.
+    fn register(&self, set: &mut ByteClassSet);
 }
 
-#[derive(Clone, Copy)]
+/*****************/
+/* Byte Classes  */
+/*****************/
+
+/// Accumulates the byte boundaries of every predicate in a pattern, then partitions the 256 byte
+/// values into equivalence classes: two bytes land in the same class iff every predicate in the
+/// pattern accepts-or-rejects them identically.
+///
+/// This is the combinator analogue of regex-automata's `classes.rs`. A byte value is a _boundary_
+/// if it begins a run of bytes that some predicate treats differently from the run before it.
+pub struct ByteClassSet {
+    boundaries: [bool; 256],
+}
+
+impl ByteClassSet {
+    fn new() -> ByteClassSet {
+        ByteClassSet {
+            boundaries: [false; 256],
+        }
+    }
+
+    /// Record that `[start, end]` is accepted by some predicate, so bytes entering and leaving the
+    /// range sit on class boundaries.
+    fn set_range(&mut self, start: u8, end: u8) {
+        self.boundaries[start as usize] = true;
+        if end < u8::MAX {
+            self.boundaries[end as usize + 1] = true;
+        }
+    }
+
+    /// Resolve the accumulated boundaries into a byte-to-class lookup table.
+    fn byte_classes(&self) -> ByteClasses {
+        let mut map = [0u8; 256];
+        let mut class = 0u8;
+        for byte in 0..256 {
+            if byte > 0 && self.boundaries[byte] {
+                class += 1;
+            }
+            map[byte] = class;
+        }
+        ByteClasses {
+            map,
+            num_classes: class as usize + 1,
+        }
+    }
+}
+
+/// A `[u8; 256]` lookup mapping each byte to its equivalence-class id, plus the number of classes.
+/// Collapsing bytes to classes shrinks a DFA transition row from 256 entries to the (usually tiny)
+/// class count, and turns predicate dispatch into a single array index.
+pub struct ByteClasses {
+    map: [u8; 256],
+    num_classes: usize,
+}
+
+impl ByteClasses {
+    /// The class id of `byte`.
+    pub fn get(&self, byte: u8) -> u8 {
+        self.map[byte as usize]
+    }
+
+    /// The number of distinct classes, i.e. the size of the reduced alphabet.
+    pub fn alphabet_len(&self) -> usize {
+        self.num_classes
+    }
+}
+
+#[derive(Clone)]
 struct SingleChar<P: Predicate> {
     predicate: P,
+    /// Which equivalence classes this predicate accepts, indexed by class id. Filled by
+    /// `install_classes` so that `advance` is a single array index rather than a predicate call.
+    accepts: Vec<bool>,
     state: SimpleState,
 }
 
@@ -66,58 +339,73 @@ impl<P: Predicate> SingleChar<P> {
     fn new(predicate: P) -> SingleChar<P> {
         SingleChar {
             predicate,
-            state: SimpleState::Neither,
+            accepts: Vec::new(),
+            state: SimpleState::new(),
         }
     }
 }
 
 impl<P: Predicate> Regex for SingleChar<P> {
     fn initialize(&mut self) {
-        self.state = SimpleState::Neither;
+        self.state = SimpleState::new();
     }
 
-    fn start(&mut self) {
-        use SimpleState::*;
+    fn seed(&mut self, _pos: usize, thread: &Thread) {
+        let waiting = self.state.waiting.take();
+        self.state.waiting = merge(waiting, Some(thread.clone()));
+    }
 
-        self.state = match self.state {
-            Neither | Start => Start,
-            Both | End => Both,
-        }
+    fn advance(&mut self, _pos: usize, class: u8) {
+        // A waiting empty string whose byte-class this predicate accepts becomes an accepting
+        // string, carrying its thread forward; everything else dies.
+        let waiting = self.state.waiting.take();
+        self.state.matched = if self.accepts[class as usize] {
+            waiting
+        } else {
+            None
+        };
     }
 
-    fn advance(&mut self, byte: u8) {
-        use SimpleState::*;
+    fn accept(&self) -> Option<Thread> {
+        self.state.matched.clone()
+    }
 
-        if self.predicate.matches(byte) {
-            self.state = match self.state {
-                Neither | End => Neither,
-                Both | Start => End,
-            };
-        } else {
-            self.state = Neither;
-        }
+    fn is_dead(&self) -> bool {
+        self.state.waiting.is_none() && self.state.matched.is_none()
     }
 
-    fn accepts(&self) -> bool {
-        use SimpleState::*;
+    fn register_bytes(&self, set: &mut ByteClassSet) {
+        self.predicate.register(set);
+    }
 
-        match self.state {
-            End | Both => true,
-            Start | Neither => false,
+    fn install_classes(&mut self, classes: &ByteClasses) {
+        // All bytes in a class are accepted-or-rejected identically, so one probe per byte collapses
+        // the predicate into a per-class table read once by `advance`.
+        let mut accepts = vec![false; classes.alphabet_len()];
+        for byte in 0..=u8::MAX {
+            if self.predicate.matches(byte) {
+                accepts[classes.get(byte) as usize] = true;
+            }
         }
+        self.accepts = accepts;
     }
+}
 
-    fn is_dead(&self) -> bool {
-        self.state == SimpleState::Neither
-    }
+/// The two kinds of string a `SingleChar` can track, each carrying its winning thread: an empty
+/// string still `waiting` to consume the char, and a string that has already `matched` it.
+#[derive(Clone)]
+struct SimpleState {
+    waiting: Option<Thread>,
+    matched: Option<Thread>,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum SimpleState {
-    Start,
-    End,
-    Both,
-    Neither,
+impl SimpleState {
+    fn new() -> SimpleState {
+        SimpleState {
+            waiting: None,
+            matched: None,
+        }
+    }
 }
 
 /***********************/
@@ -131,6 +419,10 @@ impl Predicate for Dot {
     fn matches(&self, _byte: u8) -> bool {
         true
     }
+
+    fn register(&self, _set: &mut ByteClassSet) {
+        // `Dot` accepts every byte, so it never distinguishes one byte from another.
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -140,6 +432,10 @@ impl Predicate for Byte {
     fn matches(&self, byte: u8) -> bool {
         self.0 == byte
     }
+
+    fn register(&self, set: &mut ByteClassSet) {
+        set.set_range(self.0, self.0);
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -149,42 +445,47 @@ impl Predicate for ByteRange {
     fn matches(&self, byte: u8) -> bool {
         self.0 <= byte && byte <= self.1
     }
+
+    fn register(&self, set: &mut ByteClassSet) {
+        set.set_range(self.0, self.1);
+    }
 }
 
 /*********/
 /* Empty */
 /*********/
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 struct Empty {
-    empty: bool,
+    empty: Option<Thread>,
 }
 
 impl Empty {
     fn new() -> Empty {
-        Empty { empty: false }
+        Empty { empty: None }
     }
 }
 
 impl Regex for Empty {
     fn initialize(&mut self) {
-        self.empty = false;
+        self.empty = None;
     }
 
-    fn start(&mut self) {
-        self.empty = true;
+    fn seed(&mut self, _pos: usize, thread: &Thread) {
+        let empty = self.empty.take();
+        self.empty = merge(empty, Some(thread.clone()));
     }
 
-    fn advance(&mut self, _: u8) {
-        self.empty = false;
+    fn advance(&mut self, _pos: usize, _class: u8) {
+        self.empty = None;
     }
 
-    fn accepts(&self) -> bool {
-        self.empty
+    fn accept(&self) -> Option<Thread> {
+        self.empty.clone()
     }
 
     fn is_dead(&self) -> bool {
-        !self.empty
+        self.empty.is_none()
     }
 }
 
@@ -194,14 +495,14 @@ impl Regex for Empty {
 
 #[derive(Clone)]
 struct Star<P: Regex> {
-    init: bool,
+    init: Option<Thread>,
     state: P,
 }
 
 impl<P: Regex> Star<P> {
     fn new(regex: P) -> Star<P> {
         Star {
-            init: false,
+            init: None,
             state: regex,
         }
     }
@@ -209,30 +510,44 @@ impl<P: Regex> Star<P> {
 
 impl<P: Regex> Regex for Star<P> {
     fn initialize(&mut self) {
-        self.init = false;
+        self.init = None;
         self.state.initialize();
     }
 
-    fn start(&mut self) {
-        self.init = true;
-        self.state.start();
+    fn seed(&mut self, pos: usize, thread: &Thread) {
+        let init = self.init.take();
+        self.init = merge(init, Some(thread.clone()));
+        self.state.seed(pos, thread);
     }
 
-    fn advance(&mut self, byte: u8) {
-        self.init = false;
-        self.state.advance(byte);
-        if self.state.accepts() {
-            self.init = true;
-            self.state.start();
+    fn advance(&mut self, pos: usize, class: u8) {
+        self.init = None;
+        self.state.advance(pos, class);
+        if let Some(thread) = self.state.accept() {
+            // One repetition completed; loop by seeding a fresh copy that carries its thread.
+            self.init = merge(self.init.take(), Some(thread.clone()));
+            self.state.seed(pos, &thread);
         }
     }
 
-    fn accepts(&self) -> bool {
-        self.init || self.state.accepts()
+    fn accept(&self) -> Option<Thread> {
+        merge(self.init.clone(), self.state.accept())
     }
 
     fn is_dead(&self) -> bool {
-        !self.init && self.state.is_dead()
+        self.init.is_none() && self.state.is_dead()
+    }
+
+    fn register_bytes(&self, set: &mut ByteClassSet) {
+        self.state.register_bytes(set);
+    }
+
+    fn install_classes(&mut self, classes: &ByteClasses) {
+        self.state.install_classes(classes);
+    }
+
+    fn slot_count(&self) -> usize {
+        self.state.slot_count()
     }
 }
 
@@ -242,14 +557,14 @@ impl<P: Regex> Regex for Star<P> {
 
 #[derive(Clone)]
 struct Maybe<P: Regex> {
-    init: bool,
+    init: Option<Thread>,
     state: P,
 }
 
 impl<P: Regex> Maybe<P> {
     fn new(regex: P) -> Maybe<P> {
         Maybe {
-            init: false,
+            init: None,
             state: regex,
         }
     }
@@ -257,26 +572,153 @@ impl<P: Regex> Maybe<P> {
 
 impl<P: Regex> Regex for Maybe<P> {
     fn initialize(&mut self) {
-        self.init = false;
+        self.init = None;
         self.state.initialize();
     }
 
-    fn start(&mut self) {
-        self.init = true;
-        self.state.start();
+    fn seed(&mut self, pos: usize, thread: &Thread) {
+        let init = self.init.take();
+        self.init = merge(init, Some(thread.clone()));
+        self.state.seed(pos, thread);
     }
 
-    fn advance(&mut self, byte: u8) {
-        self.init = false;
-        self.state.advance(byte);
+    fn advance(&mut self, pos: usize, class: u8) {
+        self.init = None;
+        self.state.advance(pos, class);
     }
 
-    fn accepts(&self) -> bool {
-        self.init || self.state.accepts()
+    fn accept(&self) -> Option<Thread> {
+        merge(self.init.clone(), self.state.accept())
     }
 
     fn is_dead(&self) -> bool {
-        !self.init && self.state.is_dead()
+        self.init.is_none() && self.state.is_dead()
+    }
+
+    fn register_bytes(&self, set: &mut ByteClassSet) {
+        self.state.register_bytes(set);
+    }
+
+    fn install_classes(&mut self, classes: &ByteClasses) {
+        self.state.install_classes(classes);
+    }
+
+    fn slot_count(&self) -> usize {
+        self.state.slot_count()
+    }
+}
+
+/**********/
+/* Repeat */
+/**********/
+
+/// Bounded repetition `r{min,max}`, implementing the shared-state spec directly rather than by
+/// unrolling `seq`/`maybe`, so that e.g. `a{1000,2000}` stays `O(NM)` and compact.
+///
+/// We keep a small ring of sub-state copies of `r`, one logical copy per in-flight repetition
+/// count, all advanced in lockstep. When copy `i` accepts it has completed `i + 1` repetitions, so
+/// it seeds the next copy (or, for an unbounded `max`, loops back onto the last copy just as `Star`
+/// does). The repetition accepts once at least `min` copies have completed, and is dead once every
+/// live copy has died.
+#[derive(Clone)]
+struct Repeat<P: Regex> {
+    min: usize,
+    max: Option<usize>,
+    /// The empty-string match, tracked only when `min == 0` (zero repetitions suffice).
+    init: Option<Thread>,
+    copies: Vec<P>,
+}
+
+impl<P: Regex + Clone> Repeat<P> {
+    fn new(regex: P, min: usize, max: Option<usize>) -> Repeat<P> {
+        if let Some(max) = max {
+            assert!(min <= max, "repeat: min ({}) exceeds max ({})", min, max);
+        }
+        // `max` mandatory-plus-optional copies when bounded; `min` mandatory copies plus one looping
+        // copy when unbounded.
+        let slots = match max {
+            Some(max) => max,
+            None => min + 1,
+        };
+        let copies = (0..slots).map(|_| regex.clone()).collect();
+        Repeat {
+            min,
+            max,
+            init: None,
+            copies,
+        }
+    }
+
+    /// Propagate completed repetitions forward: whenever a copy accepts, begin the next repetition
+    /// (or loop the last copy, when `max` is unbounded) carrying the completion's thread.
+    fn propagate(&mut self, pos: usize) {
+        let slots = self.copies.len();
+        for i in 0..slots {
+            if let Some(thread) = self.copies[i].accept() {
+                if self.max.is_none() && i == slots - 1 {
+                    self.copies[i].seed(pos, &thread);
+                } else if i + 1 < slots {
+                    self.copies[i + 1].seed(pos, &thread);
+                }
+            }
+        }
+    }
+}
+
+impl<P: Regex> Regex for Repeat<P> {
+    fn initialize(&mut self) {
+        self.init = None;
+        for copy in &mut self.copies {
+            copy.initialize();
+        }
+    }
+
+    fn seed(&mut self, pos: usize, thread: &Thread) {
+        if self.min == 0 {
+            let init = self.init.take();
+            self.init = merge(init, Some(thread.clone()));
+        }
+        if let Some(first) = self.copies.first_mut() {
+            first.seed(pos, thread);
+        }
+        self.propagate(pos);
+    }
+
+    fn advance(&mut self, pos: usize, class: u8) {
+        self.init = None;
+        for copy in &mut self.copies {
+            copy.advance(pos, class);
+        }
+        self.propagate(pos);
+    }
+
+    fn accept(&self) -> Option<Thread> {
+        let mut best = self.init.clone();
+        let from = self.min.saturating_sub(1);
+        for copy in &self.copies[from.min(self.copies.len())..] {
+            best = merge(best, copy.accept());
+        }
+        best
+    }
+
+    fn is_dead(&self) -> bool {
+        self.init.is_none() && self.copies.iter().all(|copy| copy.is_dead())
+    }
+
+    fn register_bytes(&self, set: &mut ByteClassSet) {
+        if let Some(first) = self.copies.first() {
+            first.register_bytes(set);
+        }
+    }
+
+    fn install_classes(&mut self, classes: &ByteClasses) {
+        for copy in &mut self.copies {
+            copy.install_classes(classes);
+        }
+    }
+
+    fn slot_count(&self) -> usize {
+        self.copies.first().map_or(0, |copy| copy.slot_count())
     }
 }
 
@@ -293,23 +735,40 @@ impl<P: Regex, Q: Regex> Regex for Alt<P, Q> {
         self.1.initialize();
     }
 
-    fn start(&mut self) {
-        self.0.start();
-        self.1.start();
+    fn seed(&mut self, pos: usize, thread: &Thread) {
+        // Left branch keeps `thread`'s priority; the right branch is demoted below it so that, when
+        // the two branches later collapse to the same state, the left (higher-priority) thread wins.
+        self.0.seed(pos, thread);
+        self.1.seed(pos, &thread.demote());
     }
 
-    fn advance(&mut self, byte: u8) {
-        self.0.advance(byte);
-        self.1.advance(byte);
+    fn advance(&mut self, pos: usize, class: u8) {
+        self.0.advance(pos, class);
+        self.1.advance(pos, class);
     }
 
-    fn accepts(&self) -> bool {
-        self.0.accepts() || self.1.accepts()
+    fn accept(&self) -> Option<Thread> {
+        // Left branch wins ties, matching leftmost-first priority.
+        merge(self.0.accept(), self.1.accept())
     }
 
     fn is_dead(&self) -> bool {
         self.0.is_dead() && self.1.is_dead()
     }
+
+    fn register_bytes(&self, set: &mut ByteClassSet) {
+        self.0.register_bytes(set);
+        self.1.register_bytes(set);
+    }
+
+    fn install_classes(&mut self, classes: &ByteClasses) {
+        self.0.install_classes(classes);
+        self.1.install_classes(classes);
+    }
+
+    fn slot_count(&self) -> usize {
+        self.0.slot_count().max(self.1.slot_count())
+    }
 }
 
 /*******/
@@ -325,49 +784,203 @@ impl<P: Regex, Q: Regex> Regex for Seq<P, Q> {
         self.1.initialize();
     }
 
-    fn start(&mut self) {
-        self.0.start();
-        if self.0.accepts() {
-            self.1.start();
+    fn seed(&mut self, pos: usize, thread: &Thread) {
+        self.0.seed(pos, thread);
+        if let Some(thread) = self.0.accept() {
+            self.1.seed(pos, &thread);
         }
     }
 
-    fn advance(&mut self, byte: u8) {
-        self.1.advance(byte);
-        self.0.advance(byte);
-        if self.0.accepts() {
-            self.1.start();
+    fn advance(&mut self, pos: usize, class: u8) {
+        self.1.advance(pos, class);
+        self.0.advance(pos, class);
+        if let Some(thread) = self.0.accept() {
+            self.1.seed(pos, &thread);
         }
     }
 
-    fn accepts(&self) -> bool {
-        self.1.accepts()
+    fn accept(&self) -> Option<Thread> {
+        self.1.accept()
     }
 
     fn is_dead(&self) -> bool {
         self.0.is_dead() && self.1.is_dead()
     }
+
+    fn register_bytes(&self, set: &mut ByteClassSet) {
+        self.0.register_bytes(set);
+        self.1.register_bytes(set);
+    }
+
+    fn install_classes(&mut self, classes: &ByteClasses) {
+        self.0.install_classes(classes);
+        self.1.install_classes(classes);
+    }
+
+    fn slot_count(&self) -> usize {
+        self.0.slot_count().max(self.1.slot_count())
+    }
+}
+
+/*********/
+/* Group */
+/*********/
+
+/// A capturing group. Records the byte range its inner regex matches into the two slots reserved
+/// for `index`: the open slot is stamped when a string enters the group, the close slot when the
+/// inner regex accepts. Threads merged at an enclosing `Alt` keep their captures, so the range
+/// reported belongs to the leftmost-first winning thread.
+#[derive(Clone)]
+struct Group<P: Regex> {
+    index: usize,
+    state: P,
+    accepting: Option<Thread>,
+}
+
+impl<P: Regex> Group<P> {
+    fn new(index: usize, regex: P) -> Group<P> {
+        Group {
+            index,
+            state: regex,
+            accepting: None,
+        }
+    }
+
+    /// Re-derive the accepting thread, stamping the close slot with the current position.
+    fn close(&mut self, pos: usize) {
+        let close = 2 * self.index + 1;
+        self.accepting = self.state.accept().map(|thread| thread.stamp(close, pos));
+    }
+}
+
+impl<P: Regex> Regex for Group<P> {
+    fn initialize(&mut self) {
+        self.state.initialize();
+        self.accepting = None;
+    }
+
+    fn seed(&mut self, pos: usize, thread: &Thread) {
+        let opened = thread.stamp(2 * self.index, pos);
+        self.state.seed(pos, &opened);
+        self.close(pos);
+    }
+
+    fn advance(&mut self, pos: usize, class: u8) {
+        self.state.advance(pos, class);
+        self.close(pos);
+    }
+
+    fn accept(&self) -> Option<Thread> {
+        self.accepting.clone()
+    }
+
+    fn is_dead(&self) -> bool {
+        self.state.is_dead()
+    }
+
+    fn register_bytes(&self, set: &mut ByteClassSet) {
+        self.state.register_bytes(set);
+    }
+
+    fn install_classes(&mut self, classes: &ByteClasses) {
+        self.state.install_classes(classes);
+    }
+
+    fn slot_count(&self) -> usize {
+        (2 * (self.index + 1)).max(self.state.slot_count())
+    }
+}
+
+/*******/
+/* Set */
+/*******/
+
+/// Runs many patterns over a single scan of the input and reports which ones match.
+///
+/// Because the `Regex` interface already advances every sub-regex in lockstep, matching a whole set
+/// at once is a natural extension: all members share one pass over the bytes, and the `is_dead()`
+/// short-circuit lets exhausted members stop consuming work.
+///
+/// Members are boxed trait objects so that patterns of _different_ concrete types can live in one
+/// set — the whole point of a set — which a `Vec<R>` of a single `R` cannot express.
+pub struct RegexSet {
+    members: Vec<Box<dyn Regex>>,
+}
+
+impl RegexSet {
+    pub fn new(members: Vec<Box<dyn Regex>>) -> RegexSet {
+        RegexSet { members }
+    }
+
+    /// The indices of the members whose pattern matches the _entire_ input.
+    pub fn matches(&mut self, input: &str) -> SmallVec<[usize; 4]> {
+        let classes = self.byte_classes();
+        for member in &mut self.members {
+            member.initialize();
+            member.install_classes(&classes);
+            member.start();
+        }
+        for (i, byte) in input.bytes().enumerate() {
+            let pos = i + 1;
+            let class = classes.get(byte);
+            let mut all_dead = true;
+            for member in &mut self.members {
+                if !member.is_dead() {
+                    member.advance(pos, class);
+                    all_dead = false;
+                }
+            }
+            if all_dead {
+                break;
+            }
+        }
+        let mut matched = SmallVec::new();
+        for (index, member) in self.members.iter().enumerate() {
+            if member.accepts() {
+                matched.push(index);
+            }
+        }
+        matched
+    }
+
+    /// Does any member match the entire input? Runs the same full scan as `matches` and checks
+    /// whether it found any index; it does not stop early once a member accepts.
+    pub fn is_match(&mut self, input: &str) -> bool {
+        !self.matches(input).is_empty()
+    }
+
+    /// Byte equivalence classes across every member of the set.
+    pub fn byte_classes(&self) -> ByteClasses {
+        let mut set = ByteClassSet::new();
+        for member in &self.members {
+            member.register_bytes(&mut set);
+        }
+        set.byte_classes()
+    }
 }
 
 pub mod combinators {
     use super::*;
 
-    pub fn empty() -> impl Regex {
+    // Constructors return `impl Regex + Clone` so composed patterns stay cloneable, which `repeat`
+    // needs to fan a sub-pattern out into one copy per in-flight repetition count.
+
+    pub fn empty() -> impl Regex + Clone {
         Empty::new()
     }
 
-    pub fn dot() -> impl Regex {
+    pub fn dot() -> impl Regex + Clone {
         SingleChar::new(Dot)
     }
 
-    pub fn byte(ch: char) -> impl Regex {
+    pub fn byte(ch: char) -> impl Regex + Clone {
         if !ch.is_ascii() {
             panic!("Char does not fit in a byte: {}", ch);
         }
         SingleChar::new(Byte(ch as u8))
     }
 
-    pub fn byte_range(min_ch: char, max_ch: char) -> impl Regex {
+    pub fn byte_range(min_ch: char, max_ch: char) -> impl Regex + Clone {
         if !min_ch.is_ascii() {
             panic!("Char does not fit in a byte: {}", min_ch);
         }
@@ -377,21 +990,33 @@ pub mod combinators {
         SingleChar::new(ByteRange(min_ch as u8, max_ch as u8))
     }
 
-    pub fn seq(first: impl Regex, second: impl Regex) -> impl Regex {
+    pub fn seq(first: impl Regex + Clone, second: impl Regex + Clone) -> impl Regex + Clone {
         Seq(first, second)
     }
 
-    pub fn alt(left: impl Regex, right: impl Regex) -> impl Regex {
+    pub fn alt(left: impl Regex + Clone, right: impl Regex + Clone) -> impl Regex + Clone {
         Alt(left, right)
     }
 
-    pub fn star(regex: impl Regex) -> impl Regex {
+    pub fn star(regex: impl Regex + Clone) -> impl Regex + Clone {
         Star::new(regex)
     }
 
-    pub fn maybe(regex: impl Regex) -> impl Regex {
+    pub fn maybe(regex: impl Regex + Clone) -> impl Regex + Clone {
         Maybe::new(regex)
     }
+
+    pub fn repeat(regex: impl Regex + Clone, min: usize, max: Option<usize>) -> impl Regex + Clone {
+        Repeat::new(regex, min, max)
+    }
+
+    pub fn group(index: usize, regex: impl Regex + Clone) -> impl Regex + Clone {
+        Group::new(index, regex)
+    }
+
+    pub fn set(members: Vec<Box<dyn Regex>>) -> RegexSet {
+        RegexSet::new(members)
+    }
 }
 
 #[cfg(test)]
@@ -443,6 +1068,141 @@ mod tests {
         assert!(!integer.is_match("1101021"));
     }
 
+    #[test]
+    fn regex_set() {
+        use combinators::*;
+
+        // All members share a single scan of the input. They are distinct concrete types, so each is
+        // boxed into a trait object.
+        let mut set = set(vec![
+            Box::new(seq(byte('a'), star(byte('b')))) as Box<dyn Regex>,
+            Box::new(star(byte_range('a', 'z'))),
+            Box::new(seq(byte('a'), byte('b'))),
+        ]);
+
+        assert_eq!(&set.matches("ab")[..], &[0, 1, 2]);
+        assert_eq!(&set.matches("abb")[..], &[0, 1]);
+        assert_eq!(&set.matches("xyz")[..], &[1]);
+        assert!(set.matches("ab!").is_empty());
+
+        assert!(set.is_match("ab"));
+        assert!(!set.is_match("ab!"));
+    }
+
+    #[test]
+    fn find_leftmost() {
+        use combinators::*;
+
+        let mut ab = seq(byte('a'), byte('b'));
+        assert_eq!(ab.find("xxabyy"), Some((2, 4)));
+        assert_eq!(ab.find("ab"), Some((0, 2)));
+        assert_eq!(ab.find("xyz"), None);
+        // Leftmost wins even when a later occurrence exists.
+        assert_eq!(ab.find("ab...ab"), Some((0, 2)));
+
+        // Leftmost-longest: the match starts as early as possible, then extends as far as possible.
+        let mut a_plus = seq(byte('a'), star(byte('a')));
+        assert_eq!(a_plus.find("baaac"), Some((1, 4)));
+
+        // An empty-matching pattern finds the empty match at offset 0.
+        let mut maybe_a = maybe(byte('a'));
+        assert_eq!(maybe_a.find("bbb"), Some((0, 0)));
+    }
+
+    #[test]
+    fn byte_classes() {
+        use combinators::*;
+
+        // `[0-9]` and `.` between them induce five classes: below '.', '.', '/', the digits, and
+        // above '9'.
+        let decimal = seq(byte('.'), star(byte_range('0', '9')));
+        let classes = decimal.byte_classes();
+
+        // Every digit shares one class; '.' sits apart, as does anything outside the predicates.
+        assert_eq!(classes.get(b'0'), classes.get(b'9'));
+        assert_eq!(classes.get(b'3'), classes.get(b'7'));
+        assert_ne!(classes.get(b'0'), classes.get(b'.'));
+        assert_ne!(classes.get(b'0'), classes.get(b'a'));
+        assert_ne!(classes.get(b'.'), classes.get(b'a'));
+
+        // Two letters the predicates never single out share a class.
+        assert_eq!(classes.get(b'a'), classes.get(b'z'));
+
+        // A pattern touching only these predicates needs far fewer than 256 classes.
+        assert!(classes.alphabet_len() <= 8);
+
+        // `dot` matches everything, so on its own there is a single class.
+        assert_eq!(dot().byte_classes().alphabet_len(), 1);
+    }
+
+    #[test]
+    fn counted_repetition() {
+        use combinators::*;
+
+        // a{2,4}
+        let mut two_four = repeat(byte('a'), 2, Some(4));
+        assert!(!two_four.is_match("a"));
+        assert!(two_four.is_match("aa"));
+        assert!(two_four.is_match("aaa"));
+        assert!(two_four.is_match("aaaa"));
+        assert!(!two_four.is_match("aaaaa"));
+        assert!(!two_four.is_match("ab"));
+
+        // a{0,2}
+        let mut zero_two = repeat(byte('a'), 0, Some(2));
+        assert!(zero_two.is_match(""));
+        assert!(zero_two.is_match("a"));
+        assert!(zero_two.is_match("aa"));
+        assert!(!zero_two.is_match("aaa"));
+
+        // a{2,} degenerates to Star after the mandatory copies.
+        let mut two_plus = repeat(byte('a'), 2, None);
+        assert!(!two_plus.is_match("a"));
+        assert!(two_plus.is_match("aa"));
+        assert!(two_plus.is_match("aaaaaaaa"));
+
+        // Multi-byte sub-pattern, large bound stays compact.
+        let mut ab_rep = repeat(seq(byte('a'), byte('b')), 1000, Some(2000));
+        assert!(ab_rep.is_match(&"ab".repeat(1000)));
+        assert!(ab_rep.is_match(&"ab".repeat(1500)));
+        assert!(!ab_rep.is_match(&"ab".repeat(999)));
+        assert!(!ab_rep.is_match(&"ab".repeat(2001)));
+    }
+
+    #[test]
+    fn captures() {
+        use combinators::*;
+
+        // (a+)(b+)
+        let mut pair = seq(
+            group(0, seq(byte('a'), star(byte('a')))),
+            group(1, seq(byte('b'), star(byte('b')))),
+        );
+        assert_eq!(pair.captures("ab"), Some(vec![Some((0, 1)), Some((1, 2))]));
+        assert_eq!(
+            pair.captures("aaabb"),
+            Some(vec![Some((0, 3)), Some((3, 5))])
+        );
+        assert_eq!(pair.captures("aaa"), None);
+        assert_eq!(pair.captures(""), None);
+
+        // An optional group that doesn't participate reports `None` for its slot.
+        let mut opt = seq(byte('a'), maybe(group(0, byte('b'))));
+        assert_eq!(opt.captures("ab"), Some(vec![Some((1, 2))]));
+        assert_eq!(opt.captures("a"), Some(vec![None]));
+
+        // Leftmost-first, not POSIX-longest: on "ab" the left `a` branch wins the equal-start tie
+        // even though the right `ab` branch would match more, so group 0 is `(0, 1)`.
+        let mut ambiguous = seq(
+            group(0, alt(byte('a'), seq(byte('a'), byte('b')))),
+            group(1, maybe(byte('b'))),
+        );
+        assert_eq!(
+            ambiguous.captures("ab"),
+            Some(vec![Some((0, 1)), Some((1, 2))])
+        );
+    }
+
     // ~4 ns / byte parsed
     #[bench]
     fn this_crate(bencher: &mut Bencher) {